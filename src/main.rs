@@ -1,12 +1,36 @@
 use std::fs::File;
 use std::io::{BufWriter, Read, Write};
 use std::process::exit;
+use std::str::FromStr;
 use std::time::{Duration, SystemTime};
 
 use clap::{App, AppSettings, Arg};
 use daemonize::Daemonize;
 use rust_util::{failure_and_exit, iff, information, util_size};
 
+type SyslogLogger = syslog::Logger<syslog::LoggerBackend, syslog::Formatter3164>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Naming {
+    Index,
+    Timestamp,
+}
+
+#[derive(Clone, Copy)]
+enum Compress {
+    Gzip,
+    Zstd,
+}
+
+impl Compress {
+    fn extension(&self) -> &'static str {
+        match self {
+            Compress::Gzip => "gz",
+            Compress::Zstd => "zst",
+        }
+    }
+}
+
 fn main() {
     let app = App::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
@@ -20,6 +44,24 @@ fn main() {
             .long("file-size").short("s").takes_value(true).default_value("10m").help("Single log file size"))
         .arg(Arg::with_name("file-count")
             .long("file-count").short("c").takes_value(true).default_value("10").help("Keep file count (from 0 to 1000)"))
+        .arg(Arg::with_name("rotate-interval")
+            .long("rotate-interval").short("t").takes_value(true).required(false).help("Also rotate when file age exceeds interval, e.g. 1h, 30m, 1d"))
+        .arg(Arg::with_name("compress")
+            .long("compress").short("z").takes_value(true).required(false).possible_values(&["gzip", "zstd"]).help("Compress rotated files in background"))
+        .arg(Arg::with_name("naming")
+            .long("naming").short("n").takes_value(true).default_value("index").possible_values(&["index", "timestamp"]).help("Rolled file naming mode"))
+        .arg(Arg::with_name("syslog")
+            .long("syslog").help("Also forward each completed line to the system logger"))
+        .arg(Arg::with_name("syslog-only")
+            .long("syslog-only").help("Forward lines to the system logger without writing rotated files"))
+        .arg(Arg::with_name("facility")
+            .long("facility").takes_value(true).default_value("user").help("Syslog facility, e.g. user, daemon, local0"))
+        .arg(Arg::with_name("timestamp")
+            .long("timestamp").short("T").takes_value(true).min_values(0).help("Prefix each line with a timestamp (default RFC3339, or a strftime format)"))
+        .arg(Arg::with_name("max-total-size")
+            .long("max-total-size").takes_value(true).required(false).help("Delete oldest files until total size is under this budget, e.g. 1g"))
+        .arg(Arg::with_name("max-age")
+            .long("max-age").takes_value(true).required(false).help("Delete files older than this age, e.g. 7d"))
         .arg(Arg::with_name("file")
             .long("file").short("F").takes_value(true).required(false).help("Read from file, default stdin"))
         .arg(Arg::with_name("continue-read")
@@ -34,10 +76,45 @@ fn main() {
     let prefix = arg_matchers.value_of("prefix").unwrap().to_string();
     let suffix = arg_matchers.value_of("suffix").unwrap().to_string();
     let file_size = arg_matchers.value_of("file-size").unwrap();
-    let file_size = util_size::parse_size(file_size).unwrap_or_else(|_| 10 * 1024 * 1028) as usize;
+    let file_size = util_size::parse_size(file_size).unwrap_or(10 * 1024 * 1028) as usize;
     let file_count = arg_matchers.value_of("file-count").unwrap();
-    let file_count = file_count.parse().unwrap_or_else(|_| 10);
-    let file_count: i32 = if file_count < 0 { 0 } else if file_count > 1000 { 1000 } else { file_count };
+    let file_count: i32 = file_count.parse().unwrap_or(10);
+    let file_count: i32 = file_count.clamp(0, 1000);
+    let rotate_interval = arg_matchers.value_of("rotate-interval")
+        .map(|d| parse_duration(d).unwrap_or_else(|e| failure_and_exit!("Parse rotate interval failed: {}", e)));
+    let compress = arg_matchers.value_of("compress").map(|c| match c {
+        "gzip" => Compress::Gzip,
+        "zstd" => Compress::Zstd,
+        _ => unreachable!(),
+    });
+    let naming = match arg_matchers.value_of("naming").unwrap() {
+        "timestamp" => Naming::Timestamp,
+        _ => Naming::Index,
+    };
+    let syslog_only = arg_matchers.is_present("syslog-only");
+    let syslog_enabled = syslog_only || arg_matchers.is_present("syslog");
+    let file_output = !syslog_only;
+    let facility = arg_matchers.value_of("facility").unwrap();
+    let syslog_facility = syslog::Facility::from_str(facility)
+        .unwrap_or_else(|_| failure_and_exit!("Parse syslog facility failed: {}", facility));
+    let syslog_tag = arg_matchers.value_of("ident").unwrap_or(env!("CARGO_PKG_NAME")).to_string();
+    // `None` disables prefixing; `Some("")` means RFC3339, `Some(fmt)` a strftime format.
+    let timestamp_format: Option<String> = if arg_matchers.is_present("timestamp") {
+        Some(arg_matchers.value_of("timestamp").unwrap_or("").to_string())
+    } else {
+        None
+    };
+    // Validate the strftime format once here rather than letting an invalid
+    // specifier panic `format!` per line and silently kill the writer thread.
+    if let Some(format) = &timestamp_format {
+        if !format.is_empty() && chrono::format::StrftimeItems::new(format).any(|item| item == chrono::format::Item::Error) {
+            failure_and_exit!("Invalid timestamp format: {}", format);
+        }
+    }
+    let max_total_size = arg_matchers.value_of("max-total-size")
+        .map(|s| util_size::parse_size(s).unwrap_or_else(|e| failure_and_exit!("Parse max total size failed: {}", e)) as u64);
+    let max_age = arg_matchers.value_of("max-age")
+        .map(|d| parse_duration(d).unwrap_or_else(|e| failure_and_exit!("Parse max age failed: {}", e)));
     let continue_read = arg_matchers.is_present("continue-read");
 
     let daemon_mode = arg_matchers.is_present("daemon");
@@ -53,7 +130,7 @@ fn main() {
             .expect("Create daemon err file failed");
         let current_dir = std::env::current_dir().expect("Get current dir failed");
         let daemonize = Daemonize::new()
-            .pid_file(&format!("/tmp/rotate-puts-daemon-{}.pid", ident))
+            .pid_file(format!("/tmp/rotate-puts-daemon-{}.pid", ident))
             // .chown_pid_file(true)      // is optional, see `Daemonize` documentation
             .working_directory(current_dir) // for default behaviour.
             // .user("nobody")
@@ -73,16 +150,62 @@ fn main() {
     information!("Prefix: {}, suffix: {}, file size: {}, file count: {}",
         prefix, suffix, util_size::get_display_size(file_size as i64), file_count
     );
+    if let Some(interval) = rotate_interval {
+        information!("Rotate interval: {}s", interval.as_secs());
+    }
+    let (compress_sender, compress_handle) = match compress {
+        Some(kind) => {
+            let (tx, rx) = std::sync::mpsc::channel::<String>();
+            let handle = std::thread::spawn(move || {
+                while let Ok(path) = rx.recv() {
+                    if let Err(e) = compress_file(&path, kind) {
+                        eprintln!("[ERROR] Compress {} failed: {}", path, e);
+                    }
+                }
+            });
+            (Some(tx), Some(handle))
+        }
+        None => (None, None),
+    };
+
     let (sender, receiver) = std::sync::mpsc::channel::<Vec<u8>>();
     std::thread::spawn(move || {
+        let mut compress_sender = compress_sender;
+        let mut compress_handle = compress_handle;
         let mut file_index = 0;
         let mut written_len = 0;
 
-        let file_name = make_new_file_name(&prefix, &suffix, file_count, &mut file_index);
-        let mut out_file = BufWriter::new(File::create(&file_name)
-            .expect(&format!("Create file failed: {}", file_name)));
+        let mut current_file_name = String::new();
+        let mut out_file: Option<BufWriter<File>> = if file_output {
+            current_file_name = make_new_file_name(&prefix, &suffix, file_count, compress, naming, max_total_size, max_age, &mut file_index);
+            Some(BufWriter::new(File::create(&current_file_name)
+                .unwrap_or_else(|_| panic!("Create file failed: {}", current_file_name))))
+        } else {
+            None
+        };
+        let mut file_open_time = SystemTime::now();
+        let mut syslog_buffer = Vec::new();
+
+        let mut syslog_logger: Option<SyslogLogger> = if syslog_enabled {
+            let formatter = syslog::Formatter3164 {
+                facility: syslog_facility,
+                hostname: None,
+                process: syslog_tag,
+                pid: 0,
+            };
+            match syslog::unix(formatter) {
+                Ok(logger) => Some(logger),
+                Err(e) => {
+                    eprintln!("[ERROR] Connect syslog failed: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
 
         let mut last_write_time = SystemTime::now();
+        let mut at_line_start = true;
         let mut write_buffer = Vec::with_capacity(1024 * 8);
         loop {
             match receiver.recv_timeout(Duration::from_secs(1)) {
@@ -92,19 +215,27 @@ fn main() {
                         Err(_) => false,
                     };
                     if should_flush_to_file && !write_buffer.is_empty() {
-                        written_len += write_buffer.len();
-                        out_file.write(&write_buffer).ok();
+                        written_len += write_chunk(&mut out_file, &mut syslog_logger, &mut syslog_buffer, &write_buffer, &timestamp_format, &mut at_line_start);
                         write_buffer.clear();
                     }
                 }
                 Ok(buff) => {
                     if buff.is_empty() {
-                        out_file.write(&write_buffer).ok();
-                        out_file.flush().ok();
+                        write_chunk(&mut out_file, &mut syslog_logger, &mut syslog_buffer, &write_buffer, &timestamp_format, &mut at_line_start);
+                        flush_syslog(&mut syslog_logger, &mut syslog_buffer);
+                        if let Some(out_file) = &mut out_file {
+                            out_file.flush().ok();
+                        }
+                        // Drop the sender and join the compress worker so any
+                        // rolled files still queued get compressed before exit.
+                        drop(compress_sender.take());
+                        if let Some(handle) = compress_handle.take() {
+                            handle.join().ok();
+                        }
                         exit(0);
                     }
                     write_buffer.extend_from_slice(&buff);
-                    let contains_new_line = write_buffer.iter().any(|c| *c == b'\n');
+                    let contains_new_line = write_buffer.contains(&b'\n');
                     if write_buffer.len() > 4 * 1024 || contains_new_line {
                         let mut pos_of_n: Option<usize> = None;
                         write_buffer.iter().enumerate().for_each(|(i, c)| {
@@ -112,25 +243,39 @@ fn main() {
                         });
                         match pos_of_n {
                             None => {
-                                written_len += write_buffer.len();
-                                out_file.write(&write_buffer).ok();
+                                written_len += write_chunk(&mut out_file, &mut syslog_logger, &mut syslog_buffer, &write_buffer, &timestamp_format, &mut at_line_start);
                                 write_buffer.clear();
                             }
                             Some(pos_of_n) => {
                                 let left_buffer = write_buffer.split_off(pos_of_n + 1);
-                                written_len += write_buffer.len();
-                                out_file.write(&write_buffer).ok();
+                                written_len += write_chunk(&mut out_file, &mut syslog_logger, &mut syslog_buffer, &write_buffer, &timestamp_format, &mut at_line_start);
                                 write_buffer = left_buffer;
                             }
                         }
-                        out_file.flush().ok();
+                        if let Some(out_file) = &mut out_file {
+                            out_file.flush().ok();
+                        }
                         last_write_time = SystemTime::now();
 
-                        if written_len >= file_size {
+                        let aged_out = match rotate_interval {
+                            Some(interval) => match SystemTime::now().duration_since(file_open_time) {
+                                Ok(age) => age >= interval,
+                                Err(_) => false,
+                            },
+                            None => false,
+                        };
+                        if file_output && (written_len >= file_size || aged_out) {
                             written_len = 0;
-                            let file_name = make_new_file_name(&prefix, &suffix, file_count, &mut file_index);
-                            out_file = BufWriter::new(File::create(&file_name)
-                                .expect(&format!("Create file failed: {}", file_name)));
+                            if let Some(out_file) = &mut out_file {
+                                out_file.flush().ok();
+                            }
+                            if let Some(tx) = &compress_sender {
+                                tx.send(current_file_name.clone()).ok();
+                            }
+                            current_file_name = make_new_file_name(&prefix, &suffix, file_count, compress, naming, max_total_size, max_age, &mut file_index);
+                            out_file = Some(BufWriter::new(File::create(&current_file_name)
+                                .unwrap_or_else(|_| panic!("Create file failed: {}", current_file_name))));
+                            file_open_time = SystemTime::now();
                         }
                     }
                 }
@@ -154,7 +299,7 @@ fn main() {
         let mut buff = [0_u8; 128];
         loop {
             match read_in.read(&mut buff) {
-                Ok(len) if len == 0 => {
+                Ok(0) => {
                     if continue_read {
                         continue 'read_open_loop; // continue and reopen file or stdin
                     } else {
@@ -175,21 +320,249 @@ fn main() {
     information!("End rotate-puts")
 }
 
-fn make_new_file_name(prefix: &str, suffix: &str, file_count: i32, index: &mut i32) -> String {
+#[allow(clippy::too_many_arguments)]
+fn make_new_file_name(prefix: &str, suffix: &str, file_count: i32, compress: Option<Compress>, naming: Naming, max_total_size: Option<u64>, max_age: Option<Duration>, index: &mut i32) -> String {
     let i = *index;
     *index = i + 1;
 
-    let pending_rm = generate_file_name(prefix, suffix, i - file_count);
-    if let Ok(_) = std::fs::metadata(&pending_rm) {
-        println!("[INFO] Remove log file: {}", &pending_rm);
-        std::fs::remove_file(&pending_rm).ok();
+    match naming {
+        Naming::Index => {
+            let pending_rm = generate_file_name(prefix, suffix, i - file_count, naming);
+            // The rolled file may already have been compressed in the background, so try
+            // its compressed variant as well when looking for the file to prune.
+            let pending_rm_variants = match compress {
+                Some(kind) => vec![format!("{}.{}", pending_rm, kind.extension()), pending_rm],
+                None => vec![pending_rm],
+            };
+            for pending_rm in pending_rm_variants {
+                if std::fs::metadata(&pending_rm).is_ok() {
+                    println!("[INFO] Remove log file: {}", &pending_rm);
+                    std::fs::remove_file(&pending_rm).ok();
+                }
+            }
+        }
+        Naming::Timestamp => prune_by_mtime(prefix, suffix, file_count),
+    }
+
+    if max_total_size.is_some() || max_age.is_some() {
+        enforce_retention(prefix, suffix, max_total_size, max_age);
     }
 
-    let file_name = generate_file_name(prefix, suffix, i);
+    let file_name = generate_file_name(prefix, suffix, i, naming);
     println!("[INFO] New log file: {}", &file_name);
     file_name
 }
 
-fn generate_file_name(prefix: &str, suffix: &str, index: i32) -> String {
-    format!("{}_{:03}{}{}", prefix, index, iff!(suffix.is_empty(), "", "."), suffix)
+// List the rotated files matching `prefix`/`suffix` (including compressed
+// variants) as `(mtime, size, path)` tuples ordered oldest first.
+fn list_rotated_files(prefix: &str, suffix: &str) -> Vec<(SystemTime, u64, std::path::PathBuf)> {
+    let (dir, base) = match prefix.rfind('/') {
+        Some(pos) => (&prefix[..pos], &prefix[pos + 1..]),
+        None => (".", prefix),
+    };
+    let base_prefix = format!("{}_", base);
+    let read_dir = match std::fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(_) => return vec![],
+    };
+    let mut matched = vec![];
+    for entry in read_dir.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let matches_suffix = suffix.is_empty() || name.contains(&format!(".{}", suffix));
+        if name.starts_with(&base_prefix) && matches_suffix {
+            if let Ok(metadata) = entry.metadata() {
+                if let Ok(mtime) = metadata.modified() {
+                    matched.push((mtime, metadata.len(), entry.path()));
+                }
+            }
+        }
+    }
+    matched.sort_by_key(|(mtime, _, _)| *mtime);
+    matched
+}
+
+// Timestamp naming has no index to subtract, so prune by listing the matching
+// files and dropping the oldest ones beyond the keep count (ordered by mtime).
+fn prune_by_mtime(prefix: &str, suffix: &str, file_count: i32) {
+    if file_count <= 0 {
+        return;
+    }
+    let matched = list_rotated_files(prefix, suffix);
+    if matched.len() as i32 >= file_count {
+        let remove_count = matched.len() as i32 - file_count + 1;
+        for (_, _, path) in matched.into_iter().take(remove_count as usize) {
+            println!("[INFO] Remove log file: {}", path.display());
+            std::fs::remove_file(&path).ok();
+        }
+    }
+}
+
+// Retention independent of file count: delete the oldest files until both the
+// total-size budget and the max-age limit are satisfied.
+fn enforce_retention(prefix: &str, suffix: &str, max_total_size: Option<u64>, max_age: Option<Duration>) {
+    let mut matched = list_rotated_files(prefix, suffix);
+
+    if let Some(max_age) = max_age {
+        let now = SystemTime::now();
+        matched.retain(|(mtime, _, path)| {
+            let expired = now.duration_since(*mtime).map(|age| age > max_age).unwrap_or(false);
+            if expired {
+                println!("[INFO] Remove log file: {}", path.display());
+                std::fs::remove_file(path).ok();
+            }
+            !expired
+        });
+    }
+
+    if let Some(max_total_size) = max_total_size {
+        let mut total: u64 = matched.iter().map(|(_, size, _)| *size).sum();
+        let mut iter = matched.into_iter();
+        while total > max_total_size {
+            match iter.next() {
+                Some((_, size, path)) => {
+                    println!("[INFO] Remove log file: {}", path.display());
+                    std::fs::remove_file(&path).ok();
+                    total = total.saturating_sub(size);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+fn compress_file(path: &str, kind: Compress) -> std::io::Result<()> {
+    let target = format!("{}.{}", path, kind.extension());
+    let mut input = File::open(path)?;
+    let output = File::create(&target)?;
+    match kind {
+        Compress::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+            std::io::copy(&mut input, &mut encoder)?;
+            encoder.finish()?;
+        }
+        Compress::Zstd => {
+            let mut encoder = zstd::stream::write::Encoder::new(output, 0)?.auto_finish();
+            std::io::copy(&mut input, &mut encoder)?;
+        }
+    }
+    std::fs::remove_file(path)?;
+    println!("[INFO] Compressed log file: {}", &target);
+    Ok(())
+}
+
+// Write a chunk to the rotated file (and syslog), applying per-line timestamp
+// prefixing when enabled. Returns the number of bytes written to the file so
+// the caller can keep `written_len` accurate for size-based rotation.
+fn write_chunk(out_file: &mut Option<BufWriter<File>>, syslog: &mut Option<SyslogLogger>, syslog_buffer: &mut Vec<u8>, chunk: &[u8], timestamp_format: &Option<String>, at_line_start: &mut bool) -> usize {
+    send_to_syslog(syslog, syslog_buffer, chunk);
+    let out_file = match out_file {
+        Some(out_file) => out_file,
+        None => return 0,
+    };
+    match timestamp_format {
+        Some(format) => {
+            let stamped = prefix_timestamp_lines(chunk, format, at_line_start);
+            out_file.write_all(&stamped).ok();
+            stamped.len()
+        }
+        None => {
+            out_file.write_all(chunk).ok();
+            chunk.len()
+        }
+    }
+}
+
+// Insert a `"<ts> "` prefix at the start of every line that begins in `bytes`,
+// including a trailing partial line that has no '\n' yet.
+// `at_line_start` carries over whether the previous chunk ended on a newline,
+// so mid-line content flushed without a trailing '\n' is not prefixed twice.
+fn prefix_timestamp_lines(bytes: &[u8], format: &str, at_line_start: &mut bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len() + 32);
+    for &b in bytes {
+        if *at_line_start {
+            // Stamp each line as it starts so lines in a multi-line chunk get
+            // their own timestamp. The format was validated at startup, so
+            // `format!` cannot panic here.
+            let now = chrono::Local::now();
+            let prefix = if format.is_empty() {
+                format!("{} ", now.to_rfc3339())
+            } else {
+                format!("{} ", now.format(format))
+            };
+            out.extend_from_slice(prefix.as_bytes());
+            *at_line_start = false;
+        }
+        out.push(b);
+        if b == b'\n' {
+            *at_line_start = true;
+        }
+    }
+    out
+}
+
+// Forward only complete (newline-terminated) lines so each logical line is one
+// syslog message even when a line is flushed across a timeout or partial-buffer
+// path; the trailing remainder stays buffered until its newline arrives.
+fn send_to_syslog(logger: &mut Option<SyslogLogger>, buffer: &mut Vec<u8>, bytes: &[u8]) {
+    let logger = match logger {
+        Some(logger) => logger,
+        None => return,
+    };
+    buffer.extend_from_slice(bytes);
+    if let Some(pos) = buffer.iter().rposition(|c| *c == b'\n') {
+        let complete: Vec<u8> = buffer.drain(..=pos).collect();
+        for line in complete.split(|c| *c == b'\n') {
+            if line.is_empty() {
+                continue;
+            }
+            logger.info(String::from_utf8_lossy(line).into_owned()).ok();
+        }
+    }
+    // Cap the remainder the same way the file path caps `write_buffer`, so a
+    // newline-less producer can't grow this buffer without bound.
+    if buffer.len() > 4 * 1024 {
+        logger.info(String::from_utf8_lossy(buffer).into_owned()).ok();
+        buffer.clear();
+    }
+}
+
+// Flush any buffered remainder (a final line without a trailing newline) when
+// the input stream ends.
+fn flush_syslog(logger: &mut Option<SyslogLogger>, buffer: &mut Vec<u8>) {
+    let logger = match logger {
+        Some(logger) => logger,
+        None => return,
+    };
+    if !buffer.is_empty() {
+        logger.info(String::from_utf8_lossy(buffer).into_owned()).ok();
+        buffer.clear();
+    }
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (digits, unit) = value.split_at(value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len()));
+    if digits.is_empty() {
+        return Err(format!("Invalid duration: {}", value));
+    }
+    let count: u64 = digits.parse().map_err(|_| format!("Invalid duration: {}", value))?;
+    let seconds = match unit {
+        "" | "s" => count,
+        "m" => count * 60,
+        "h" => count * 60 * 60,
+        "d" => count * 60 * 60 * 24,
+        _ => return Err(format!("Invalid duration unit: {}", unit)),
+    };
+    Ok(Duration::from_secs(seconds))
+}
+
+fn generate_file_name(prefix: &str, suffix: &str, index: i32, naming: Naming) -> String {
+    let stem = match naming {
+        Naming::Index => format!("{}_{:03}", prefix, index),
+        // The index is appended as a discriminant so two rolls in the same
+        // second (bursty volume under size-based rotation) never collide and
+        // truncate an existing file via `File::create`.
+        Naming::Timestamp => format!("{}_{}_{:03}", prefix, chrono::Local::now().format("%Y-%m-%d_%H-%M-%S"), index),
+    };
+    format!("{}{}{}", stem, iff!(suffix.is_empty(), "", "."), suffix)
 }